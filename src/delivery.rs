@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+use tokio::sync::Semaphore;
+
+use crate::Notification;
+
+/// How `notify` hands a freshly received notification off to storage and
+/// rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// Store and schedule the notification synchronously, on the calling
+    /// D-Bus handler task. This is the original behavior.
+    Immediate,
+    /// Push the notification onto a bounded queue and return immediately;
+    /// a dedicated worker task drains and renders it instead.
+    Queued,
+}
+
+/// What happens when a `Queued` dispatch queue is already at capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Wait for the worker to free up space.
+    Block,
+    /// Drop the oldest queued notification to make room for the new one.
+    DropOldest,
+}
+
+pub struct DeliveryConfig {
+    pub mode: DeliveryMode,
+    pub capacity: usize,
+    pub batch_size: usize,
+    pub backpressure: BackpressurePolicy,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            mode: DeliveryMode::Immediate,
+            capacity: 1024,
+            batch_size: 5000,
+            backpressure: BackpressurePolicy::Block,
+        }
+    }
+}
+
+/// Something that can turn a batch of notifications into whatever the user
+/// actually sees (or persists) — a popup renderer, a history store, a test
+/// double, and so on.
+pub trait RenderBackend: Send + Sync {
+    fn render_batch(&self, batch: &[Notification]);
+}
+
+/// Bounded FIFO of pending notifications, guarded by a `Mutex` for the data
+/// plus a pair of counting `Semaphore`s that track free slots and ready
+/// items. Semaphore permits (unlike `Notify::notify_waiters`) are stored
+/// rather than dropped when nobody happens to be waiting yet, so a producer
+/// and the worker can never miss each other's wakeup.
+pub struct DispatchQueue {
+    capacity: usize,
+    backpressure: BackpressurePolicy,
+    queue: Mutex<VecDeque<Notification>>,
+    /// Free slots remaining; only consulted under `BackpressurePolicy::Block`.
+    free_slots: Semaphore,
+    /// Items ready to be drained by the worker.
+    items_ready: Semaphore,
+}
+
+impl DispatchQueue {
+    pub fn new(capacity: usize, backpressure: BackpressurePolicy) -> Self {
+        Self {
+            capacity,
+            backpressure,
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            free_slots: Semaphore::new(capacity),
+            items_ready: Semaphore::new(0),
+        }
+    }
+
+    /// Enqueues `notification`, applying the configured backpressure policy
+    /// once the queue is full.
+    pub async fn push(&self, notification: Notification) {
+        if self.backpressure == BackpressurePolicy::Block {
+            // Reserve a slot before touching the queue; the permit is
+            // returned once the worker drains an item, so this blocks until
+            // genuine space opens up rather than racing a lost wakeup.
+            self.free_slots
+                .acquire()
+                .await
+                .expect("free_slots semaphore is never closed")
+                .forget();
+
+            let mut queue = self.queue.lock().unwrap();
+            queue.push_back(notification);
+            drop(queue);
+            self.items_ready.add_permits(1);
+        } else {
+            let mut queue = self.queue.lock().unwrap();
+            // Only grant a new `items_ready` permit when the queue actually
+            // grows. When it's already full, the dropped item's permit is
+            // simply inherited by the notification that replaces it in the
+            // queue — granting another here would let the worker eventually
+            // acquire a permit with nothing left to drain.
+            let grew = queue.len() < self.capacity;
+            if !grew {
+                if let Some(dropped) = queue.pop_front() {
+                    debug!(
+                        "Dispatch queue full, dropping oldest notification #{}",
+                        dropped.id
+                    );
+                }
+            }
+            queue.push_back(notification);
+            drop(queue);
+            if grew {
+                self.items_ready.add_permits(1);
+            }
+        }
+    }
+
+    /// Waits for at least one queued notification, then drains up to
+    /// `max_batch` of them at once.
+    pub async fn next_batch(&self, max_batch: usize) -> Vec<Notification> {
+        let first = self
+            .items_ready
+            .acquire()
+            .await
+            .expect("items_ready semaphore is never closed");
+        first.forget();
+
+        let mut acquired = 1;
+        while acquired < max_batch {
+            match self.items_ready.try_acquire() {
+                Ok(permit) => {
+                    permit.forget();
+                    acquired += 1;
+                }
+                Err(_) => break,
+            }
+        }
+
+        let mut queue = self.queue.lock().unwrap();
+        let batch: Vec<_> = queue.drain(..acquired.min(queue.len())).collect();
+        drop(queue);
+
+        if self.backpressure == BackpressurePolicy::Block {
+            self.free_slots.add_permits(batch.len());
+        }
+
+        batch
+    }
+}
+
+/// Drains `queue` forever, handing each coalesced batch to `backend`.
+pub async fn run_worker(queue: Arc<DispatchQueue>, batch_size: usize, backend: Arc<dyn RenderBackend>) {
+    loop {
+        let batch = queue.next_batch(batch_size).await;
+        debug!("Dispatch worker rendering a batch of {} notifications", batch.len());
+        backend.render_batch(&batch);
+    }
+}
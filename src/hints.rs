@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use zbus::zvariant::Value;
+
+/// Decoded `urgency` hint (freedesktop spec: `0` low, `1` normal, `2`
+/// critical). Anything else collapses to `Normal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Urgency::Low,
+            2 => Urgency::Critical,
+            _ => Urgency::Normal,
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        match self {
+            Urgency::Low => 0,
+            Urgency::Normal => 1,
+            Urgency::Critical => 2,
+        }
+    }
+}
+
+/// Decoded `image-data`/`icon_data` hint: the raw `(iiibiiay)` struct the
+/// spec defines for inline icon pixel data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageData {
+    pub width: i32,
+    pub height: i32,
+    pub rowstride: i32,
+    pub has_alpha: bool,
+    pub bits_per_sample: i32,
+    pub channels: i32,
+    pub data: Vec<u8>,
+}
+
+/// Structured view over the freedesktop notification `hints` map. Standard
+/// keys get a typed field; anything else is preserved in `unknown` rather
+/// than silently dropped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationHints {
+    pub urgency: Option<Urgency>,
+    pub category: Option<String>,
+    pub desktop_entry: Option<String>,
+    pub image_path: Option<String>,
+    pub image_data: Option<ImageData>,
+    pub sound_name: Option<String>,
+    pub suppress_sound: bool,
+    pub transient: bool,
+    pub resident: bool,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    pub unknown: HashMap<String, String>,
+}
+
+impl NotificationHints {
+    pub fn urgency_or_normal(&self) -> Urgency {
+        self.urgency.unwrap_or(Urgency::Normal)
+    }
+}
+
+/// Decodes the standard freedesktop hint keys out of the raw `hints` map
+/// `notify` receives over D-Bus.
+pub fn parse_hints(hints: HashMap<&str, Value<'_>>) -> NotificationHints {
+    let mut parsed = NotificationHints::default();
+
+    for (key, value) in hints {
+        match key {
+            "urgency" => match value.downcast_ref::<u8>() {
+                Ok(urgency) => parsed.urgency = Some(Urgency::from_u8(urgency)),
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "category" => match value.downcast_ref::<&str>() {
+                Ok(category) => parsed.category = Some(category.to_string()),
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "desktop-entry" => match value.downcast_ref::<&str>() {
+                Ok(entry) => parsed.desktop_entry = Some(entry.to_string()),
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "image-path" | "image_path" => match value.downcast_ref::<&str>() {
+                Ok(path) => parsed.image_path = Some(path.to_string()),
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "image-data" | "image_data" | "icon_data" => match parse_image_data(&value) {
+                Some(image_data) => parsed.image_data = Some(image_data),
+                None => remember_unknown(&mut parsed, key, &value),
+            },
+            "sound-name" => match value.downcast_ref::<&str>() {
+                Ok(name) => parsed.sound_name = Some(name.to_string()),
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "suppress-sound" => match value.downcast_ref::<bool>() {
+                Ok(suppress) => parsed.suppress_sound = suppress,
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "transient" => match value.downcast_ref::<bool>() {
+                Ok(transient) => parsed.transient = transient,
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "resident" => match value.downcast_ref::<bool>() {
+                Ok(resident) => parsed.resident = resident,
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "x" => match value.downcast_ref::<i32>() {
+                Ok(x) => parsed.x = Some(x),
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            "y" => match value.downcast_ref::<i32>() {
+                Ok(y) => parsed.y = Some(y),
+                Err(_) => remember_unknown(&mut parsed, key, &value),
+            },
+            _ => remember_unknown(&mut parsed, key, &value),
+        }
+    }
+
+    parsed
+}
+
+fn remember_unknown(parsed: &mut NotificationHints, key: &str, value: &Value<'_>) {
+    debug!("Unrecognized or undecodable hint '{}': {:?}", key, value);
+    parsed.unknown.insert(key.to_string(), format!("{:?}", value));
+}
+
+fn parse_image_data(value: &Value<'_>) -> Option<ImageData> {
+    let Value::Structure(structure) = value else {
+        return None;
+    };
+
+    let fields = structure.fields();
+    if fields.len() != 7 {
+        return None;
+    }
+
+    let width = fields[0].downcast_ref::<i32>().ok()?;
+    let height = fields[1].downcast_ref::<i32>().ok()?;
+    let rowstride = fields[2].downcast_ref::<i32>().ok()?;
+    let has_alpha = fields[3].downcast_ref::<bool>().ok()?;
+    let bits_per_sample = fields[4].downcast_ref::<i32>().ok()?;
+    let channels = fields[5].downcast_ref::<i32>().ok()?;
+    let data = fields[6]
+        .downcast_ref::<zbus::zvariant::Array>()
+        .ok()?
+        .iter()
+        .filter_map(|byte| byte.downcast_ref::<u8>().ok())
+        .collect();
+
+    Some(ImageData {
+        width,
+        height,
+        rowstride,
+        has_alpha,
+        bits_per_sample,
+        channels,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use zbus::zvariant::{Array, StructureBuilder};
+
+    #[test]
+    fn parses_urgency_category_and_booleans() {
+        let mut hints: HashMap<&str, Value<'_>> = HashMap::new();
+        hints.insert("urgency", Value::from(2u8));
+        hints.insert("category", Value::from("device.added"));
+        hints.insert("transient", Value::from(true));
+        hints.insert("resident", Value::from(false));
+        hints.insert("x", Value::from(10i32));
+        hints.insert("y", Value::from(20i32));
+
+        let parsed = parse_hints(hints);
+
+        assert_eq!(parsed.urgency, Some(Urgency::Critical));
+        assert_eq!(parsed.category.as_deref(), Some("device.added"));
+        assert!(parsed.transient);
+        assert!(!parsed.resident);
+        assert_eq!(parsed.x, Some(10));
+        assert_eq!(parsed.y, Some(20));
+        assert!(parsed.unknown.is_empty());
+    }
+
+    #[test]
+    fn decodes_inline_image_data() {
+        let pixels = Array::try_from(vec![10u8, 20, 30, 40]).expect("u8 array");
+        let image = StructureBuilder::new()
+            .add_field(32i32)
+            .add_field(16i32)
+            .add_field(128i32)
+            .add_field(true)
+            .add_field(8i32)
+            .add_field(4i32)
+            .add_field(pixels)
+            .build();
+
+        let mut hints: HashMap<&str, Value<'_>> = HashMap::new();
+        hints.insert("icon_data", Value::Structure(image));
+
+        let parsed = parse_hints(hints);
+
+        let image_data = parsed.image_data.expect("image-data should decode");
+        assert_eq!(image_data.width, 32);
+        assert_eq!(image_data.height, 16);
+        assert_eq!(image_data.rowstride, 128);
+        assert!(image_data.has_alpha);
+        assert_eq!(image_data.bits_per_sample, 8);
+        assert_eq!(image_data.channels, 4);
+        assert_eq!(image_data.data, vec![10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn preserves_unknown_keys_in_fallback_map() {
+        let mut hints: HashMap<&str, Value<'_>> = HashMap::new();
+        hints.insert("x-vendor-extra", Value::from(7u32));
+
+        let parsed = parse_hints(hints);
+
+        assert!(parsed.urgency.is_none());
+        assert_eq!(parsed.unknown.len(), 1);
+        assert!(parsed.unknown.contains_key("x-vendor-extra"));
+    }
+}
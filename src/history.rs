@@ -0,0 +1,451 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write as _};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::hints::NotificationHints;
+use crate::Notification;
+
+/// Bounds how much the on-disk log is allowed to grow.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub max_count: usize,
+    pub max_age: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_count: 500,
+            max_age: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// One line of the on-disk history log. The log is append-only; a
+/// notification's full lifecycle is reconstructed by folding its `Created`
+/// event together with a later `Closed` event, if any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum HistoryEvent {
+    Created {
+        id: u32,
+        app_name: String,
+        summary: String,
+        body: String,
+        icon: String,
+        expire_timeout: i32,
+        actions: Vec<(String, String)>,
+        hints: NotificationHints,
+        created_at: u64,
+    },
+    Closed {
+        id: u32,
+        reason: u32,
+        closed_at: u64,
+    },
+}
+
+/// A notification's reconstructed lifecycle, as returned by `get_history`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub id: u32,
+    pub app_name: String,
+    pub summary: String,
+    pub body: String,
+    pub icon: String,
+    pub expire_timeout: i32,
+    pub actions: Vec<(String, String)>,
+    pub hints: NotificationHints,
+    pub created_at: u64,
+    pub closed_at: Option<u64>,
+    pub close_reason: Option<u32>,
+}
+
+pub fn unix_millis_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Append-only JSONL history log under the XDG data dir, with an
+/// in-memory index kept in sync for fast queries.
+pub struct HistoryStore {
+    log_path: PathBuf,
+    log_file: Mutex<File>,
+    records: Mutex<VecDeque<HistoryRecord>>,
+    retention: RetentionPolicy,
+}
+
+impl HistoryStore {
+    /// Opens (creating if necessary) the log at `log_path`, replaying it
+    /// into memory and applying `retention` immediately.
+    pub fn open(log_path: PathBuf, retention: RetentionPolicy) -> std::io::Result<Self> {
+        if let Some(parent) = log_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let records = Mutex::new(replay(&log_path)?);
+
+        let log_file = Mutex::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&log_path)?,
+        );
+
+        let store = Self {
+            log_path,
+            log_file,
+            records,
+            retention,
+        };
+        store.enforce_retention();
+        Ok(store)
+    }
+
+    /// Notifications from the log that hadn't closed by the time the
+    /// daemon last shut down, for replaying into the active set on
+    /// startup.
+    pub fn open_records(&self) -> Vec<HistoryRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|record| record.closed_at.is_none())
+            .cloned()
+            .collect()
+    }
+
+    pub fn record_created(&self, notification: &Notification, created_at: u64) {
+        let event = HistoryEvent::Created {
+            id: notification.id,
+            app_name: notification.app_name.clone(),
+            summary: notification.summary.clone(),
+            body: notification.body.clone(),
+            icon: notification.icon.clone(),
+            expire_timeout: notification.expire_timeout,
+            actions: notification.actions.clone(),
+            hints: notification.hints.clone(),
+            created_at,
+        };
+
+        self.records.lock().unwrap().push_back(HistoryRecord {
+            id: notification.id,
+            app_name: notification.app_name.clone(),
+            summary: notification.summary.clone(),
+            body: notification.body.clone(),
+            icon: notification.icon.clone(),
+            expire_timeout: notification.expire_timeout,
+            actions: notification.actions.clone(),
+            hints: notification.hints.clone(),
+            created_at,
+            closed_at: None,
+            close_reason: None,
+        });
+
+        self.append(&event);
+        self.trim_in_memory();
+    }
+
+    pub fn record_closed(&self, id: u32, reason: u32, closed_at: u64) {
+        {
+            let mut records = self.records.lock().unwrap();
+            // `id` alone isn't unique across history: `replaces_id` reuses
+            // the same id for an unrelated later notification, so match the
+            // newest *still-open* record rather than merely the newest one,
+            // or a close could attach to an instance it was never about.
+            if let Some(record) = records
+                .iter_mut()
+                .rev()
+                .find(|record| record.id == id && record.closed_at.is_none())
+            {
+                record.closed_at = Some(closed_at);
+                record.close_reason = Some(reason);
+            }
+        }
+
+        self.append(&HistoryEvent::Closed {
+            id,
+            reason,
+            closed_at,
+        });
+    }
+
+    /// Returns up to `limit` history records with `created_at >= since`,
+    /// most recent first.
+    pub fn get_history(&self, limit: usize, since: u64) -> Vec<HistoryRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|record| record.created_at >= since)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Wipes both the in-memory index and the on-disk log.
+    pub fn clear_history(&self) {
+        self.records.lock().unwrap().clear();
+
+        match File::create(&self.log_path) {
+            Ok(file) => *self.log_file.lock().unwrap() = file,
+            Err(e) => error!("Failed to truncate history log {}: {}", self.log_path.display(), e),
+        }
+    }
+
+    /// Drops in-memory records beyond `max_count` or older than `max_age`.
+    /// Cheap enough to run on every `record_created`, since it never touches
+    /// disk — the log itself is only ever appended to outside of
+    /// `enforce_retention` and `clear_history`.
+    fn trim_in_memory(&self) {
+        let now = unix_millis_now();
+        let max_age_millis = self.retention.max_age.as_millis() as u64;
+
+        let mut records = self.records.lock().unwrap();
+        while records.len() > self.retention.max_count {
+            records.pop_front();
+        }
+        records.retain(|record| now.saturating_sub(record.created_at) <= max_age_millis);
+    }
+
+    /// Trims in-memory records, then rewrites the on-disk log to match.
+    /// This is an O(n) rewrite of the whole log, so it's reserved for the
+    /// reaper's periodic sweep rather than run after every notification.
+    pub fn enforce_retention(&self) {
+        self.trim_in_memory();
+
+        let records = self.records.lock().unwrap();
+        let rewritten = compact(&self.log_path, &records);
+        drop(records);
+
+        match rewritten {
+            Ok(file) => *self.log_file.lock().unwrap() = file,
+            Err(e) => warn!("Failed to compact history log {}: {}", self.log_path.display(), e),
+        }
+    }
+
+    fn append(&self, event: &HistoryEvent) {
+        let Ok(mut line) = serde_json::to_vec(event) else {
+            error!("Failed to serialize history event for #{:?}", event);
+            return;
+        };
+        line.push(b'\n');
+
+        if let Err(e) = self.log_file.lock().unwrap().write_all(&line) {
+            error!("Failed to append to history log {}: {}", self.log_path.display(), e);
+        }
+    }
+}
+
+fn replay(log_path: &std::path::Path) -> std::io::Result<VecDeque<HistoryRecord>> {
+    let file = match File::open(log_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(VecDeque::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut records: VecDeque<HistoryRecord> = VecDeque::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event: HistoryEvent = match serde_json::from_str(&line) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Skipping malformed history log line: {}", e);
+                continue;
+            }
+        };
+
+        match event {
+            HistoryEvent::Created {
+                id,
+                app_name,
+                summary,
+                body,
+                icon,
+                expire_timeout,
+                actions,
+                hints,
+                created_at,
+            } => records.push_back(HistoryRecord {
+                id,
+                app_name,
+                summary,
+                body,
+                icon,
+                expire_timeout,
+                actions,
+                hints,
+                created_at,
+                closed_at: None,
+                close_reason: None,
+            }),
+            HistoryEvent::Closed { id, reason, closed_at } => {
+                // Match the newest still-open record for this id, not just
+                // the newest record — `replaces_id` reuse means a later,
+                // unrelated `Created` can share the same id.
+                if let Some(record) = records
+                    .iter_mut()
+                    .rev()
+                    .find(|record| record.id == id && record.closed_at.is_none())
+                {
+                    record.closed_at = Some(closed_at);
+                    record.close_reason = Some(reason);
+                }
+            }
+        }
+    }
+
+    debug!("Replayed {} history record(s) from {}", records.len(), log_path.display());
+    Ok(records)
+}
+
+fn compact(log_path: &std::path::Path, records: &VecDeque<HistoryRecord>) -> std::io::Result<File> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(log_path)?;
+
+    for record in records {
+        let created = HistoryEvent::Created {
+            id: record.id,
+            app_name: record.app_name.clone(),
+            summary: record.summary.clone(),
+            body: record.body.clone(),
+            icon: record.icon.clone(),
+            expire_timeout: record.expire_timeout,
+            actions: record.actions.clone(),
+            hints: record.hints.clone(),
+            created_at: record.created_at,
+        };
+        let mut line = serde_json::to_vec(&created).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+
+        if let (Some(closed_at), Some(reason)) = (record.closed_at, record.close_reason) {
+            let closed = HistoryEvent::Closed {
+                id: record.id,
+                reason,
+                closed_at,
+            };
+            let mut line = serde_json::to_vec(&closed).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+        }
+    }
+
+    OpenOptions::new().append(true).open(log_path)
+}
+
+/// Default history log path: `$XDG_DATA_HOME/dusty/history.jsonl`, falling
+/// back to `~/.local/share/dusty/history.jsonl`.
+pub fn default_log_path() -> PathBuf {
+    if let Ok(data_home) = std::env::var("XDG_DATA_HOME") {
+        return PathBuf::from(data_home).join("dusty").join("history.jsonl");
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        return PathBuf::from(home)
+            .join(".local/share/dusty/history.jsonl");
+    }
+    PathBuf::from("dusty-history.jsonl")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+
+    fn write_log(path: &std::path::Path, lines: &[&str]) {
+        let mut file = File::create(path).unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+    }
+
+    #[test]
+    fn replay_returns_empty_for_missing_log() {
+        let path = std::env::temp_dir().join("dusty-history-test-missing.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        let records = replay(&path).unwrap();
+
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn replay_folds_closed_event_into_its_created_record() {
+        let path = std::env::temp_dir().join("dusty-history-test-fold.jsonl");
+        write_log(
+            &path,
+            &[
+                r#"{"event":"created","id":1,"app_name":"a","summary":"s","body":"b","icon":"","expire_timeout":5000,"actions":[],"hints":{"urgency":null,"category":null,"desktop_entry":null,"image_path":null,"image_data":null,"sound_name":null,"suppress_sound":false,"transient":false,"resident":false,"x":null,"y":null,"unknown":{}},"created_at":100}"#,
+                r#"{"event":"closed","id":1,"reason":2,"closed_at":200}"#,
+            ],
+        );
+
+        let records = replay(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.id, 1);
+        assert_eq!(record.closed_at, Some(200));
+        assert_eq!(record.close_reason, Some(2));
+    }
+
+    #[test]
+    fn replay_attaches_close_to_the_right_instance_when_id_is_reused() {
+        // `replaces_id` reuse means the same id can be created, closed, and
+        // then created again later for an unrelated notification.
+        let path = std::env::temp_dir().join("dusty-history-test-reused-id.jsonl");
+        write_log(
+            &path,
+            &[
+                r#"{"event":"created","id":1,"app_name":"a","summary":"first","body":"b","icon":"","expire_timeout":5000,"actions":[],"hints":{"urgency":null,"category":null,"desktop_entry":null,"image_path":null,"image_data":null,"sound_name":null,"suppress_sound":false,"transient":false,"resident":false,"x":null,"y":null,"unknown":{}},"created_at":100}"#,
+                r#"{"event":"closed","id":1,"reason":4,"closed_at":150}"#,
+                r#"{"event":"created","id":1,"app_name":"a","summary":"second","body":"b","icon":"","expire_timeout":5000,"actions":[],"hints":{"urgency":null,"category":null,"desktop_entry":null,"image_path":null,"image_data":null,"sound_name":null,"suppress_sound":false,"transient":false,"resident":false,"x":null,"y":null,"unknown":{}},"created_at":300}"#,
+            ],
+        );
+
+        let records = replay(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].summary, "first");
+        assert_eq!(records[0].closed_at, Some(150));
+        assert_eq!(records[0].close_reason, Some(4));
+        assert_eq!(records[1].summary, "second");
+        assert_eq!(records[1].closed_at, None);
+    }
+
+    #[test]
+    fn replay_leaves_unclosed_notifications_open() {
+        let path = std::env::temp_dir().join("dusty-history-test-open.jsonl");
+        write_log(
+            &path,
+            &[
+                r#"{"event":"created","id":7,"app_name":"a","summary":"s","body":"b","icon":"","expire_timeout":0,"actions":[],"hints":{"urgency":null,"category":null,"desktop_entry":null,"image_path":null,"image_data":null,"sound_name":null,"suppress_sound":false,"transient":false,"resident":false,"x":null,"y":null,"unknown":{}},"created_at":50}"#,
+            ],
+        );
+
+        let records = replay(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].closed_at, None);
+        assert_eq!(records[0].close_reason, None);
+    }
+}
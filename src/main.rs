@@ -1,14 +1,33 @@
+mod delivery;
+mod hints;
+mod history;
+mod reaper;
+mod subscription;
+
 use std::error::Error;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::select;
-use zbus::{Connection, interface};
+use zbus::object_server::SignalEmitter;
+use zbus::{interface, Connection};
 use log::{info, warn, error, debug, LevelFilter};
 use chrono::Local;
 use env_logger::Builder;
 use std::io::Write;
 
+use delivery::{DeliveryConfig, DeliveryMode, DispatchQueue, RenderBackend};
+use hints::{parse_hints, NotificationHints};
+use history::{unix_millis_now, HistoryStore, RetentionPolicy};
+use reaper::{expire_deadline, expire_duration, EvictionListener, Reaper, RemovalCause};
+use subscription::{SubscriptionEvent, SubscriptionHub};
+
+/// Object path Dusty publishes the notifications interface on; shared with
+/// the reaper so it can build its own `SignalEmitter` outside of any D-Bus
+/// method call.
+pub(crate) const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+
 #[derive(Debug, Clone)]
 struct Notification {
     id: u32,
@@ -17,22 +36,210 @@ struct Notification {
     body: String,
     icon: String,
     expire_timeout: i32,
+    deadline: Option<Instant>,
+    actions: Vec<(String, String)>,
+    hints: NotificationHints,
+}
+
+/// Splits the flat `action_key, label, action_key, label, ...` list the
+/// spec requires into key/label pairs.
+fn parse_actions(actions: &[&str]) -> Vec<(String, String)> {
+    actions
+        .chunks_exact(2)
+        .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+        .collect()
+}
+
+/// Eviction listener that just logs; registered by default so every
+/// eviction shows up in the debug log.
+struct LoggingEvictionListener;
+
+impl EvictionListener for LoggingEvictionListener {
+    fn on_evict(&self, id: u32, cause: RemovalCause) {
+        debug!("Notification #{} evicted: {:?}", id, cause);
+    }
 }
 
 #[derive(Clone)]
 struct NotificationDaemon {
     notifications: Arc<Mutex<HashMap<u32, Notification>>>,
     next_id: Arc<Mutex<u32>>,
+    reaper: Arc<Reaper>,
+    eviction_listeners: Arc<Mutex<Vec<Arc<dyn EvictionListener>>>>,
+    /// Set once the session bus connection is established, so background
+    /// tasks (the reaper) can emit signals without a D-Bus method context.
+    connection: Arc<OnceLock<Connection>>,
+    delivery_mode: DeliveryMode,
+    /// Only present when `delivery_mode` is `Queued`.
+    dispatch_queue: Option<Arc<DispatchQueue>>,
+    subscription_hub: Arc<SubscriptionHub>,
+    history: Arc<HistoryStore>,
+}
+
+/// Default cap on concurrent live-stream subscribers, used unless
+/// `DUSTY_MAX_SUBSCRIBERS` says otherwise.
+const DEFAULT_MAX_SUBSCRIBERS: usize = 32;
+
+/// Hands a queued-mode batch back to the daemon's own store-and-schedule
+/// path, so `Immediate` and `Queued` delivery share one code path for what
+/// "storing" a notification means.
+struct DaemonStoreBackend {
+    daemon: NotificationDaemon,
+}
+
+impl RenderBackend for DaemonStoreBackend {
+    fn render_batch(&self, batch: &[Notification]) {
+        for notification in batch {
+            self.daemon.store_and_schedule(notification.clone());
+        }
+    }
 }
 
 impl NotificationDaemon {
-    fn new() -> Self {
-        Self {
+    fn with_config(delivery: DeliveryConfig, max_subscribers: usize, history: Arc<HistoryStore>) -> Self {
+        let dispatch_queue = match delivery.mode {
+            DeliveryMode::Immediate => None,
+            DeliveryMode::Queued => Some(Arc::new(DispatchQueue::new(delivery.capacity, delivery.backpressure))),
+        };
+
+        let daemon = Self {
             notifications: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(1)),
+            reaper: Arc::new(Reaper::new()),
+            eviction_listeners: Arc::new(Mutex::new(vec![Arc::new(LoggingEvictionListener)])),
+            connection: Arc::new(OnceLock::new()),
+            delivery_mode: delivery.mode,
+            dispatch_queue,
+            subscription_hub: SubscriptionHub::new(max_subscribers),
+            history,
+        };
+        daemon.replay_history();
+        daemon
+    }
+
+    /// Reloads notifications from the history log that hadn't closed by
+    /// the time the daemon last shut down, arming each one's deadline with
+    /// whatever part of its expiry duration hasn't already elapsed. Entries
+    /// whose time has already run out are not reloaded, and no close is
+    /// recorded for them in the history log.
+    fn replay_history(&self) {
+        let now_millis = unix_millis_now();
+
+        for record in self.history.open_records() {
+            let urgency = record.hints.urgency_or_normal();
+            let total = expire_duration(
+                record.expire_timeout,
+                urgency,
+                record.hints.transient,
+                record.hints.resident,
+            );
+
+            let deadline = match total {
+                None => None,
+                Some(total) => {
+                    let elapsed = Duration::from_millis(now_millis.saturating_sub(record.created_at));
+                    match total.checked_sub(elapsed) {
+                        Some(remaining) if !remaining.is_zero() => Some(Instant::now() + remaining),
+                        _ => {
+                            debug!("Notification #{} expired while the daemon was down", record.id);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            let notification = Notification {
+                id: record.id,
+                app_name: record.app_name,
+                summary: record.summary,
+                body: record.body,
+                icon: record.icon,
+                expire_timeout: record.expire_timeout,
+                deadline,
+                actions: record.actions,
+                hints: record.hints,
+            };
+
+            let id = notification.id;
+            self.notifications.lock().unwrap().insert(id, notification);
+            if let Some(deadline) = deadline {
+                self.reaper.schedule(id, deadline);
+            }
+        }
+
+        let max_existing_id = self.notifications.lock().unwrap().keys().copied().max();
+        if let Some(max_id) = max_existing_id {
+            let mut next_id = self.next_id.lock().unwrap();
+            if *next_id == 1 {
+                *next_id = max_id.wrapping_add(1).max(1);
+            }
         }
     }
 
+    /// Registers an additional callback invoked whenever a notification
+    /// leaves the active set, regardless of cause.
+    fn add_eviction_listener(&self, listener: Arc<dyn EvictionListener>) {
+        self.eviction_listeners.lock().unwrap().push(listener);
+    }
+
+    fn notify_evicted(&self, id: u32, cause: RemovalCause) {
+        for listener in self.eviction_listeners.lock().unwrap().iter() {
+            listener.on_evict(id, cause);
+        }
+    }
+
+    /// Inserts `notification` into the active set, arms its reaper deadline
+    /// if it has one, and reports a replacement to eviction listeners. This
+    /// is the single place that storing a newly received notification
+    /// happens, whether it got here via the immediate path or the queued
+    /// dispatch worker; startup replay inserts into the active set itself.
+    fn store_and_schedule(&self, notification: Notification) {
+        let id = notification.id;
+        let deadline = notification.deadline;
+        let view = subscription::NotificationView::from(&notification);
+
+        let replaced = self.notifications.lock().unwrap().contains_key(&id);
+        if replaced {
+            // 4 = undefined/reserved, per the freedesktop spec; there's no
+            // dedicated "superseded" reason, so this is what other daemons
+            // use for a replace as well.
+            let now = unix_millis_now();
+            self.history.record_closed(id, 4, now);
+            self.notify_evicted(id, RemovalCause::Replaced);
+            self.subscription_hub
+                .publish(SubscriptionEvent::Closed { id, reason: 4 });
+            self.emit_notification_closed(id, 4);
+        }
+
+        self.history.record_created(&notification, unix_millis_now());
+        self.notifications.lock().unwrap().insert(id, notification);
+
+        if let Some(deadline) = deadline {
+            self.reaper.schedule(id, deadline);
+        }
+
+        self.subscription_hub.publish(SubscriptionEvent::New(view));
+    }
+
+    /// Fires the `NotificationClosed` D-Bus signal from outside a method
+    /// call context, the same way the reaper does for expiry evictions.
+    fn emit_notification_closed(&self, id: u32, reason: u32) {
+        let Some(connection) = self.connection.get().cloned() else {
+            return;
+        };
+
+        tokio::spawn(async move {
+            match SignalEmitter::new(&connection, OBJECT_PATH) {
+                Ok(emitter) => {
+                    if let Err(e) = Self::notification_closed(&emitter, id, reason).await {
+                        warn!("Failed to emit NotificationClosed for #{}: {}", id, e);
+                    }
+                }
+                Err(e) => warn!("Failed to build signal emitter for #{}: {}", id, e),
+            }
+        });
+    }
+
     fn next_id(&self) -> u32 {
         let mut id = self.next_id.lock().unwrap();
         let current_id = *id;
@@ -63,7 +270,19 @@ impl NotificationDaemon {
         expire_timeout: i32,
     ) -> u32 {
         let id = if replaces_id > 0 { replaces_id } else { self.next_id() };
-        
+
+        let hints = parse_hints(hints);
+        debug!("Hints for notification #{}: {:?}", id, hints);
+
+        let now = Instant::now();
+        let deadline = expire_deadline(
+            expire_timeout,
+            hints.urgency_or_normal(),
+            hints.transient,
+            hints.resident,
+            now,
+        );
+
         let notification = Notification {
             id,
             app_name: app_name.to_string(),
@@ -71,24 +290,28 @@ impl NotificationDaemon {
             body: body.to_string(),
             icon: app_icon.to_string(),
             expire_timeout,
+            deadline,
+            actions: parse_actions(&actions),
+            hints,
         };
-        
+
         info!("Notification #{} from {}: {} - {}", id, app_name, summary, body);
-        
+
         if !actions.is_empty() {
             debug!("Actions available for notification #{}: {:?}", id, actions);
         }
-        
-        for (key, value) in hints {
-            if key == "urgency" {
-                if let Ok(urgency) = value.downcast_ref::<u8>() {
-                    debug!("Urgency for notification #{}: {}", id, urgency);
-                }
+
+        match self.delivery_mode {
+            DeliveryMode::Immediate => self.store_and_schedule(notification),
+            DeliveryMode::Queued => {
+                let queue = self
+                    .dispatch_queue
+                    .as_ref()
+                    .expect("dispatch_queue is set whenever delivery_mode is Queued");
+                queue.push(notification).await;
             }
         }
-        
-        self.notifications.lock().unwrap().insert(id, notification);
-        
+
         id
     }
 
@@ -102,7 +325,11 @@ impl NotificationDaemon {
         ]
     }
 
-    async fn close_notification(&self, id: u32) {
+    async fn close_notification(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        id: u32,
+    ) -> zbus::fdo::Result<()> {
         let notification_closed = {
             let mut notifications = self.notifications.lock().unwrap();
             if let Some(notification) = notifications.remove(&id) {
@@ -113,10 +340,51 @@ impl NotificationDaemon {
                 false
             }
         };
-        
+
         if notification_closed {
-            debug!("Emitted NotificationClosed signal for #{}", id);
+            self.notify_evicted(id, RemovalCause::ClosedByClient);
+            // 3 = closed by a CloseNotification call, per the freedesktop spec.
+            Self::notification_closed(&emitter, id, 3).await?;
+            self.subscription_hub
+                .publish(SubscriptionEvent::Closed { id, reason: 3 });
+            self.history.record_closed(id, 3, unix_millis_now());
         }
+
+        Ok(())
+    }
+
+    /// Returns up to `limit` past notifications created at or after
+    /// `since` (milliseconds since the Unix epoch), most recent first.
+    /// Each entry is `(id, app_name, summary, body, icon, expire_timeout,
+    /// created_at, is_closed, closed_at, close_reason)`.
+    async fn get_history(
+        &self,
+        limit: u32,
+        since: u64,
+    ) -> Vec<(u32, String, String, String, String, i32, u64, bool, u64, u32)> {
+        self.history
+            .get_history(limit as usize, since)
+            .into_iter()
+            .map(|record| {
+                (
+                    record.id,
+                    record.app_name,
+                    record.summary,
+                    record.body,
+                    record.icon,
+                    record.expire_timeout,
+                    record.created_at,
+                    record.closed_at.is_some(),
+                    record.closed_at.unwrap_or(0),
+                    record.close_reason.unwrap_or(0),
+                )
+            })
+            .collect()
+    }
+
+    async fn clear_history(&self) {
+        info!("Clearing notification history");
+        self.history.clear_history();
     }
 
     async fn get_server_information(&self) -> (String, String, String, String) {
@@ -128,6 +396,44 @@ impl NotificationDaemon {
             "1.2".to_string(),
         )
     }
+
+    /// Invokes an action previously advertised by a notification, emitting
+    /// `ActionInvoked` so the originating client can react to it.
+    async fn invoke_action(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        id: u32,
+        action_key: &str,
+    ) -> zbus::fdo::Result<()> {
+        let known = {
+            let notifications = self.notifications.lock().unwrap();
+            notifications
+                .get(&id)
+                .map(|notification| notification.actions.iter().any(|(key, _)| key == action_key))
+                .unwrap_or(false)
+        };
+
+        if !known {
+            return Err(zbus::fdo::Error::Failed(format!(
+                "Notification #{} has no action '{}'",
+                id, action_key
+            )));
+        }
+
+        debug!("Invoking action '{}' for notification #{}", action_key, id);
+        Self::action_invoked(&emitter, id, action_key).await?;
+        self.subscription_hub.publish(SubscriptionEvent::ActionInvoked {
+            id,
+            action_key: action_key.to_string(),
+        });
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn notification_closed(emitter: &SignalEmitter<'_>, id: u32, reason: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn action_invoked(emitter: &SignalEmitter<'_>, id: u32, action_key: &str) -> zbus::Result<()>;
 }
 
 fn setup_logger() -> Result<(), log::SetLoggerError> {
@@ -146,13 +452,80 @@ fn setup_logger() -> Result<(), log::SetLoggerError> {
     Ok(())
 }
 
+/// Reads `DUSTY_DELIVERY_MODE` (`"immediate"` or `"queued"`, case
+/// insensitive) to pick the delivery mode, defaulting to `Immediate` when
+/// unset or unrecognized.
+fn delivery_config_from_env() -> DeliveryConfig {
+    let mode = match std::env::var("DUSTY_DELIVERY_MODE") {
+        Ok(value) if value.eq_ignore_ascii_case("queued") => DeliveryMode::Queued,
+        _ => DeliveryMode::Immediate,
+    };
+    DeliveryConfig {
+        mode,
+        ..DeliveryConfig::default()
+    }
+}
+
+/// Reads `DUSTY_MAX_SUBSCRIBERS` to cap concurrent live-stream
+/// subscribers, defaulting to `DEFAULT_MAX_SUBSCRIBERS` when unset or
+/// unparsable.
+fn max_subscribers_from_env() -> usize {
+    std::env::var("DUSTY_MAX_SUBSCRIBERS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SUBSCRIBERS)
+}
+
+/// Reads `DUSTY_HISTORY_PATH` for the history log location, defaulting to
+/// `history::default_log_path()` when unset.
+fn history_path_from_env() -> std::path::PathBuf {
+    std::env::var("DUSTY_HISTORY_PATH")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|_| history::default_log_path())
+}
+
 async fn run_daemon() -> Result<(), Box<dyn Error + Send + Sync>> {
-    let daemon = NotificationDaemon::new();
-    
+    let delivery_config = delivery_config_from_env();
+    let batch_size = delivery_config.batch_size;
+    let history = Arc::new(HistoryStore::open(
+        history_path_from_env(),
+        RetentionPolicy::default(),
+    )?);
+    let daemon = NotificationDaemon::with_config(delivery_config, max_subscribers_from_env(), history);
+
     let daemon_stats = daemon.clone();
 
     let connection = Connection::session().await?;
-    
+    daemon
+        .connection
+        .set(connection.clone())
+        .expect("connection is only set once, right after it's established");
+
+    tokio::spawn(daemon.reaper.clone().run(
+        daemon.notifications.clone(),
+        daemon.eviction_listeners.clone(),
+        daemon.connection.clone(),
+        daemon.subscription_hub.clone(),
+        daemon.history.clone(),
+    ));
+
+    if let Some(queue) = daemon.dispatch_queue.clone() {
+        let backend = Arc::new(DaemonStoreBackend {
+            daemon: daemon.clone(),
+        });
+        tokio::spawn(delivery::run_worker(queue, batch_size, backend));
+    }
+
+    let socket_path = std::env::var("DUSTY_SUBSCRIBE_SOCKET")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|_| subscription::default_socket_path());
+    let subscription_hub = daemon.subscription_hub.clone();
+    tokio::spawn(async move {
+        if let Err(e) = subscription::serve_unix(subscription_hub, socket_path).await {
+            error!("Subscription socket stopped: {}", e);
+        }
+    });
+
     match connection.request_name("org.freedesktop.Notifications").await {
         Ok(_) => {
             info!("Successfully acquired D-Bus name");
@@ -168,7 +541,7 @@ async fn run_daemon() -> Result<(), Box<dyn Error + Send + Sync>> {
     
     connection
         .object_server()
-        .at("/org/freedesktop/Notifications", daemon)
+        .at(OBJECT_PATH, daemon)
         .await?;
 
     info!("Dusty notification daemon is running");
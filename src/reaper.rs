@@ -0,0 +1,282 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use tokio::sync::Notify;
+use zbus::object_server::SignalEmitter;
+use zbus::Connection;
+
+use crate::hints::Urgency;
+use crate::history::{unix_millis_now, HistoryStore};
+use crate::subscription::{SubscriptionEvent, SubscriptionHub};
+use crate::{Notification, NotificationDaemon, OBJECT_PATH};
+
+/// Why a notification was removed from the daemon's active set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalCause {
+    /// `expire_timeout` elapsed without the client closing it first.
+    Expired,
+    /// A later `Notify` call reused the same id before this one expired.
+    Replaced,
+    /// The client explicitly called `CloseNotification`.
+    ClosedByClient,
+}
+
+/// Receives a callback whenever a notification leaves the active set.
+///
+/// Mirrors the removal-listener pattern from caches like moka: a single
+/// hook point for any consumer that wants to observe evictions. The
+/// `NotificationClosed` signal, subscription events and history records
+/// are emitted directly by the daemon and the reaper, not through this
+/// hook.
+pub trait EvictionListener: Send + Sync {
+    fn on_evict(&self, id: u32, cause: RemovalCause);
+}
+
+impl<F> EvictionListener for F
+where
+    F: Fn(u32, RemovalCause) + Send + Sync,
+{
+    fn on_evict(&self, id: u32, cause: RemovalCause) {
+        self(id, cause)
+    }
+}
+
+/// Turns an `expire_timeout` value from the spec into an absolute deadline.
+///
+/// Per the freedesktop notification spec: `> 0` is milliseconds until
+/// auto-close, `0` means never expire, and `-1` means the server picks a
+/// default (here, scaled by urgency). `resident` notifications are kept
+/// around regardless of timeout; `transient` ones always get a deadline,
+/// even when `expire_timeout` asked for "never", since they're meant to be
+/// fleeting (e.g. volume/brightness OSDs).
+pub fn expire_deadline(
+    expire_timeout: i32,
+    urgency: Urgency,
+    transient: bool,
+    resident: bool,
+    now: Instant,
+) -> Option<Instant> {
+    expire_duration(expire_timeout, urgency, transient, resident).map(|duration| now + duration)
+}
+
+/// Same as `expire_deadline`, but returns the duration rather than an
+/// absolute `Instant` so callers recovering from a restart (where "now"
+/// isn't when the notification was created) can subtract elapsed time
+/// themselves.
+pub fn expire_duration(
+    expire_timeout: i32,
+    urgency: Urgency,
+    transient: bool,
+    resident: bool,
+) -> Option<Duration> {
+    if resident {
+        return None;
+    }
+
+    let effective_timeout = if transient && expire_timeout == 0 {
+        -1
+    } else {
+        expire_timeout
+    };
+
+    match effective_timeout {
+        0 => None,
+        -1 => default_duration(urgency, transient),
+        ms if ms > 0 => Some(Duration::from_millis(ms as u64)),
+        _ => Some(Duration::from_millis(5_000)),
+    }
+}
+
+/// The server-chosen default for `expire_timeout == -1`, scaled by urgency:
+/// low-urgency notifications clear fastest, critical ones need a human to
+/// dismiss them and so never auto-expire — except transient notifications
+/// (e.g. volume/brightness OSDs), which always get a deadline regardless of
+/// urgency, since they're meant to be fleeting.
+fn default_duration(urgency: Urgency, transient: bool) -> Option<Duration> {
+    match urgency {
+        Urgency::Low => Some(Duration::from_millis(3_000)),
+        Urgency::Normal => Some(Duration::from_millis(5_000)),
+        Urgency::Critical if transient => Some(Duration::from_millis(10_000)),
+        Urgency::Critical => None,
+    }
+}
+
+/// Min-ordered timer structure that wakes a background loop to evict
+/// notifications once their `expire_timeout` deadline passes.
+///
+/// The heap can hold stale entries for ids whose deadline was later
+/// replaced or cancelled; `run` resolves that lazily by checking each
+/// popped entry against the notification's current deadline before
+/// treating it as due.
+pub struct Reaper {
+    deadlines: Mutex<BinaryHeap<Reverse<(Instant, u32)>>>,
+    wake: Notify,
+}
+
+impl Reaper {
+    pub fn new() -> Self {
+        Self {
+            deadlines: Mutex::new(BinaryHeap::new()),
+            wake: Notify::new(),
+        }
+    }
+
+    /// Arms a deadline for `id` and wakes the reaper loop, so it re-arms
+    /// its sleep on whichever deadline is now the soonest.
+    pub fn schedule(&self, id: u32, deadline: Instant) {
+        self.deadlines.lock().unwrap().push(Reverse((deadline, id)));
+        self.wake.notify_one();
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines
+            .lock()
+            .unwrap()
+            .peek()
+            .map(|Reverse((deadline, _))| *deadline)
+    }
+
+    fn pop_due(&self, now: Instant) -> Vec<(Instant, u32)> {
+        let mut due = Vec::new();
+        let mut deadlines = self.deadlines.lock().unwrap();
+        while let Some(&Reverse((deadline, id))) = deadlines.peek() {
+            if deadline > now {
+                break;
+            }
+            deadlines.pop();
+            due.push((deadline, id));
+        }
+        due
+    }
+
+    /// Runs until the process shuts down, evicting notifications whose
+    /// deadline has passed, emitting `NotificationClosed` and firing
+    /// `listeners` for each one. Also doubles as the history log's
+    /// periodic retention sweep, since both jobs are "wake up occasionally
+    /// and bound something that grows over time".
+    pub async fn run(
+        self: Arc<Self>,
+        notifications: Arc<Mutex<HashMap<u32, Notification>>>,
+        listeners: Arc<Mutex<Vec<Arc<dyn EvictionListener>>>>,
+        connection: Arc<OnceLock<Connection>>,
+        subscriptions: Arc<SubscriptionHub>,
+        history: Arc<HistoryStore>,
+    ) {
+        let mut retention_tick = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            tokio::select! {
+                _ = self.wait_for_next_deadline() => {
+                    self.evict_due(&notifications, &listeners, &connection, &subscriptions, &history).await;
+                }
+                _ = retention_tick.tick() => {
+                    history.enforce_retention();
+                }
+            }
+        }
+    }
+
+    async fn wait_for_next_deadline(&self) {
+        match self.next_deadline() {
+            Some(deadline) => {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(deadline.into()) => {}
+                    _ = self.wake.notified() => {}
+                }
+            }
+            None => self.wake.notified().await,
+        }
+    }
+
+    async fn evict_due(
+        &self,
+        notifications: &Mutex<HashMap<u32, Notification>>,
+        listeners: &Mutex<Vec<Arc<dyn EvictionListener>>>,
+        connection: &OnceLock<Connection>,
+        subscriptions: &SubscriptionHub,
+        history: &HistoryStore,
+    ) {
+        let now = Instant::now();
+        for (deadline, id) in self.pop_due(now) {
+            let removed = {
+                let mut notifications = notifications.lock().unwrap();
+                match notifications.get(&id) {
+                    Some(notification) if notification.deadline == Some(deadline) => {
+                        notifications.remove(&id)
+                    }
+                    _ => None,
+                }
+            };
+
+            if let Some(notification) = removed {
+                debug!("Notification #{} from {} expired", id, notification.app_name);
+
+                if let Some(connection) = connection.get() {
+                    match SignalEmitter::new(connection, OBJECT_PATH) {
+                        Ok(emitter) => {
+                            // 1 = expired, per the freedesktop notification spec.
+                            if let Err(e) = NotificationDaemon::notification_closed(&emitter, id, 1).await {
+                                warn!("Failed to emit NotificationClosed for #{}: {}", id, e);
+                            }
+                        }
+                        Err(e) => warn!("Failed to build signal emitter for #{}: {}", id, e),
+                    }
+                }
+
+                for listener in listeners.lock().unwrap().iter() {
+                    listener.on_evict(id, RemovalCause::Expired);
+                }
+
+                // 1 = expired, per the freedesktop notification spec.
+                subscriptions.publish(SubscriptionEvent::Closed { id, reason: 1 });
+                history.record_closed(id, 1, unix_millis_now());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resident_never_expires_regardless_of_timeout() {
+        assert_eq!(expire_duration(5000, Urgency::Normal, false, true), None);
+        assert_eq!(expire_duration(-1, Urgency::Critical, true, true), None);
+    }
+
+    #[test]
+    fn zero_means_never_expire_unless_transient() {
+        assert_eq!(expire_duration(0, Urgency::Normal, false, false), None);
+        assert!(expire_duration(0, Urgency::Normal, true, false).is_some());
+    }
+
+    #[test]
+    fn explicit_positive_timeout_is_used_verbatim() {
+        assert_eq!(
+            expire_duration(7000, Urgency::Low, false, false),
+            Some(Duration::from_millis(7000))
+        );
+    }
+
+    #[test]
+    fn default_duration_does_not_make_low_urgency_outlast_normal() {
+        let low = expire_duration(-1, Urgency::Low, false, false).unwrap();
+        let normal = expire_duration(-1, Urgency::Normal, false, false).unwrap();
+        assert!(
+            low <= normal,
+            "low-urgency default ({:?}) should not linger longer than normal ({:?})",
+            low,
+            normal
+        );
+    }
+
+    #[test]
+    fn critical_never_auto_expires_unless_transient() {
+        assert_eq!(expire_duration(-1, Urgency::Critical, false, false), None);
+        assert!(expire_duration(-1, Urgency::Critical, true, false).is_some());
+    }
+}
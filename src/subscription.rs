@@ -0,0 +1,221 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use log::{info, warn};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc, watch};
+
+use crate::hints::ImageData;
+use crate::Notification;
+
+/// Per-connection channel depth; bounds how far a slow subscriber can fall
+/// behind before the dispatcher starts dropping events for it.
+const SUBSCRIBER_CHANNEL_CAPACITY: usize = 64;
+/// How many events the internal history bus retains for the dispatcher
+/// while it is momentarily busy fanning out the previous one.
+const HISTORY_CAPACITY: usize = 1024;
+
+/// Wire representation of the `image-data`/`icon_data` hint.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageDataView {
+    pub width: i32,
+    pub height: i32,
+    pub rowstride: i32,
+    pub has_alpha: bool,
+    pub bits_per_sample: i32,
+    pub channels: i32,
+    pub data: Vec<u8>,
+}
+
+impl From<&ImageData> for ImageDataView {
+    fn from(image: &ImageData) -> Self {
+        Self {
+            width: image.width,
+            height: image.height,
+            rowstride: image.rowstride,
+            has_alpha: image.has_alpha,
+            bits_per_sample: image.bits_per_sample,
+            channels: image.channels,
+            data: image.data.clone(),
+        }
+    }
+}
+
+/// Wire representation of a `Notification`; kept separate from the
+/// internal struct so reaper-only bookkeeping (like `deadline`) never
+/// leaks onto the socket.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationView {
+    pub id: u32,
+    pub app_name: String,
+    pub summary: String,
+    pub body: String,
+    pub icon: String,
+    pub expire_timeout: i32,
+    pub image_data: Option<ImageDataView>,
+}
+
+impl From<&Notification> for NotificationView {
+    fn from(notification: &Notification) -> Self {
+        Self {
+            id: notification.id,
+            app_name: notification.app_name.clone(),
+            summary: notification.summary.clone(),
+            body: notification.body.clone(),
+            icon: notification.icon.clone(),
+            expire_timeout: notification.expire_timeout,
+            image_data: notification.hints.image_data.as_ref().map(ImageDataView::from),
+        }
+    }
+}
+
+/// One observable event, in the order the daemon produced it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SubscriptionEvent {
+    New(NotificationView),
+    Closed { id: u32, reason: u32 },
+    ActionInvoked { id: u32, action_key: String },
+}
+
+/// Backs the live notification stream consumed by bars, overlays and
+/// history panels. Holds the latest event for late joiners (`watch`) and
+/// the full event history on an internal bus (`broadcast`) whose only
+/// receiver is a single dispatcher task; it drains that bus and fans each event out to
+/// every connected client with a non-blocking `try_send`, so one
+/// slow/blocked client can't stall delivery to the rest.
+pub struct SubscriptionHub {
+    latest: watch::Sender<Option<SubscriptionEvent>>,
+    history: broadcast::Sender<SubscriptionEvent>,
+    subscribers: Mutex<Vec<mpsc::Sender<SubscriptionEvent>>>,
+    max_connections: usize,
+}
+
+impl SubscriptionHub {
+    pub fn new(max_connections: usize) -> Arc<Self> {
+        let (latest, _) = watch::channel(None);
+        let (history, _) = broadcast::channel(HISTORY_CAPACITY);
+
+        let hub = Arc::new(Self {
+            latest,
+            history,
+            subscribers: Mutex::new(Vec::new()),
+            max_connections,
+        });
+
+        tokio::spawn(hub.clone().run_dispatcher());
+
+        hub
+    }
+
+    /// Records `event` as the latest state and hands it to the dispatcher.
+    pub fn publish(&self, event: SubscriptionEvent) {
+        let _ = self.latest.send(Some(event.clone()));
+        let _ = self.history.send(event);
+    }
+
+    async fn run_dispatcher(self: Arc<Self>) {
+        let mut history = self.history.subscribe();
+        loop {
+            let event = match history.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Subscription dispatcher lagged, {} events dropped", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+
+            let senders = self.subscribers.lock().unwrap().clone();
+            for sender in &senders {
+                // `try_send` so a subscriber whose socket is backed up (channel
+                // full) never blocks delivery to the rest; we simply drop the
+                // event for them, same as a lagged broadcast receiver would.
+                if let Err(mpsc::error::TrySendError::Full(_)) = sender.try_send(event.clone()) {
+                    warn!("Dropping event for a backed-up subscriber");
+                }
+            }
+
+            self.subscribers.lock().unwrap().retain(|sender| !sender.is_closed());
+        }
+    }
+
+    fn register(&self) -> Option<mpsc::Receiver<SubscriptionEvent>> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|sender| !sender.is_closed());
+
+        if subscribers.len() >= self.max_connections {
+            return None;
+        }
+
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_CHANNEL_CAPACITY);
+        subscribers.push(tx);
+        Some(rx)
+    }
+
+    fn latest_snapshot(&self) -> Option<SubscriptionEvent> {
+        self.latest.subscribe().borrow().clone()
+    }
+}
+
+/// Accepts subscriber connections on `socket_path` until the process
+/// shuts down, spawning one task per connection.
+pub async fn serve_unix(hub: Arc<SubscriptionHub>, socket_path: impl AsRef<Path>) -> std::io::Result<()> {
+    let socket_path = socket_path.as_ref();
+    let _ = std::fs::remove_file(socket_path);
+    let listener = UnixListener::bind(socket_path)?;
+    info!("Subscription socket listening at {}", socket_path.display());
+
+    loop {
+        let (stream, _addr) = listener.accept().await?;
+
+        let Some(mut receiver) = hub.register() else {
+            warn!(
+                "Rejecting subscriber: max_connections ({}) reached",
+                hub.max_connections
+            );
+            continue;
+        };
+
+        let latest = hub.latest_snapshot();
+        tokio::spawn(async move {
+            handle_subscriber(stream, latest, &mut receiver).await;
+        });
+    }
+}
+
+async fn handle_subscriber(
+    mut stream: UnixStream,
+    latest: Option<SubscriptionEvent>,
+    receiver: &mut mpsc::Receiver<SubscriptionEvent>,
+) {
+    if let Some(event) = latest {
+        if write_event(&mut stream, &event).await.is_err() {
+            return;
+        }
+    }
+
+    while let Some(event) = receiver.recv().await {
+        if write_event(&mut stream, &event).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn write_event(stream: &mut UnixStream, event: &SubscriptionEvent) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(event)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    line.push(b'\n');
+    stream.write_all(&line).await
+}
+
+/// Default socket path: `$XDG_RUNTIME_DIR/dusty.sock`, falling back to
+/// `/tmp/dusty.sock` when the runtime dir isn't set.
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(runtime_dir) => PathBuf::from(runtime_dir).join("dusty.sock"),
+        Err(_) => PathBuf::from("/tmp/dusty.sock"),
+    }
+}